@@ -19,7 +19,7 @@
 
 use crate::vec2::AxialDisplacement;
 
-use super::state::{Id, InventoryList};
+use super::state::{Id, InventoryList, TurnPhase};
 
 pub enum Order {
     Production(Production),
@@ -142,6 +142,51 @@ pub struct Burn {
     pub direction: AxialDisplacement,
 }
 
-pub fn parse_orders(orders: &String) -> Result<Vec<Order>, &'static str> {
+/// Splits the turn number and phase the orders were written against off the front of an orders packet
+pub fn parse_orders_header(orders: &str) -> Result<(u64, TurnPhase, &str), &'static str> {
+    let mut parts = orders.splitn(3, '\n');
+    let number = parts
+        .next()
+        .and_then(|number| number.parse::<u64>().ok())
+        .ok_or("invalid turn number in orders packet")?;
+    let phase: TurnPhase = parts
+        .next()
+        .ok_or("missing turn phase in orders packet")?
+        .try_into()?;
+    Ok((number, phase, parts.next().unwrap_or_default()))
+}
+
+pub fn parse_orders(orders: &str) -> Result<Vec<Order>, &'static str> {
     todo!();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_is_split_from_orders() {
+        let (number, phase, orders) =
+            parse_orders_header("12\ncombat\nfirst order\nsecond order").unwrap();
+        assert_eq!(number, 12);
+        assert!(phase == TurnPhase::Combat);
+        assert_eq!(orders, "first order\nsecond order");
+    }
+
+    #[test]
+    fn header_without_orders_has_empty_body() {
+        let (number, phase, orders) = parse_orders_header("0\neconomic").unwrap();
+        assert_eq!(number, 0);
+        assert!(phase == TurnPhase::Economic);
+        assert_eq!(orders, "");
+    }
+
+    #[test]
+    fn malformed_headers_are_rejected() {
+        assert!(parse_orders_header("").is_err());
+        assert!(parse_orders_header("-1\ncombat\n").is_err());
+        assert!(parse_orders_header("twelve\ncombat\n").is_err());
+        assert!(parse_orders_header("12").is_err());
+        assert!(parse_orders_header("12\nCombat\n").is_err());
+    }
+}
@@ -18,10 +18,11 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashSet},
     fmt::Display,
     fs,
     ops::Mul,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use rand::{seq::SliceRandom, thread_rng, Rng};
@@ -39,8 +40,26 @@ use super::order::Order;
 mod celestial;
 mod stack;
 
-#[derive(Eq, PartialEq, Hash, Copy, Clone, Serialize, Deserialize)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Copy, Clone, Serialize, Deserialize)]
 pub struct Id(u64);
+impl Id {
+    /// Short base-36 form of this id, for log lines and bug reports
+    pub fn short(&self) -> String {
+        const DIGITS: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+        let mut value = self.0;
+        let mut digits = Vec::new();
+        loop {
+            digits.push(DIGITS[(value % 36) as usize]);
+            value /= 36;
+            if value == 0 {
+                break;
+            }
+        }
+        digits.reverse();
+        String::from_utf8(digits).expect("base-36 digits should be valid utf-8")
+    }
+}
 impl From<Id> for String {
     fn from(value: Id) -> Self {
         value.0.to_string()
@@ -64,13 +83,13 @@ impl Default for IdGenerator {
     }
 }
 
-#[derive(Serialize, Deserialize, Hash, Eq, PartialEq, Copy, Clone)]
+#[derive(Serialize, Deserialize, Hash, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
 pub struct Owner(u8);
 impl TryFrom<u8> for Owner {
     type Error = &'static str;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
-        if value > GameState::MAX_PLAYERS {
+        if value >= GameState::MAX_PLAYERS {
             Err("value too high")
         } else {
             Ok(Owner(value))
@@ -88,13 +107,36 @@ impl Display for Owner {
     }
 }
 
-#[derive(Serialize, Deserialize, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Eq, PartialEq, Copy, Clone)]
 pub enum TurnPhase {
     Economic,
     Ordnance,
     Combat,
     Movement,
 }
+impl TryFrom<&str> for TurnPhase {
+    type Error = &'static str;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "economic" => Ok(TurnPhase::Economic),
+            "ordnance" => Ok(TurnPhase::Ordnance),
+            "combat" => Ok(TurnPhase::Combat),
+            "movement" => Ok(TurnPhase::Movement),
+            _ => Err("unknown phase"),
+        }
+    }
+}
+impl Display for TurnPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TurnPhase::Economic => write!(f, "economic"),
+            TurnPhase::Ordnance => write!(f, "ordnance"),
+            TurnPhase::Combat => write!(f, "combat"),
+            TurnPhase::Movement => write!(f, "movement"),
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize)]
 struct Turn {
@@ -121,6 +163,14 @@ impl Turn {
     }
 }
 
+/// When a phase finished resolving, in seconds since the unix epoch
+#[derive(Serialize, Deserialize)]
+struct PhaseTimestamp {
+    number: u64,
+    phase: TurnPhase,
+    resolved_at: u64,
+}
+
 pub enum SerializedState {
     MutualLoss,
     Winner(Owner),
@@ -214,17 +264,24 @@ impl Mul<u64> for InventoryList {
 #[derive(Serialize, Deserialize)]
 pub struct GameState {
     /// maps between player id and username
-    players: HashMap<Owner, Option<String>>,
+    players: BTreeMap<Owner, Option<String>>,
     turn: Turn,
     id_generator: IdGenerator,
-    stacks: HashMap<Id, Stack>,
-    ordnance: HashMap<Id, Ordnance>,
-    celestials: HashMap<Id, CelestialBody>,
-    asteroids: HashMap<Id, AsteroidField>,
+    stacks: BTreeMap<Id, Stack>,
+    ordnance: BTreeMap<Id, Ordnance>,
+    celestials: BTreeMap<Id, CelestialBody>,
+    asteroids: BTreeMap<Id, AsteroidField>,
+    /// number of consecutive full turns that have ended in a stalemate
+    #[serde(default)]
+    stalemate_turns: u64,
+    #[serde(default)]
+    phase_timestamps: Vec<PhaseTimestamp>,
 }
 impl GameState {
-    const MIN_PLAYERS: u8 = 2;
-    const MAX_PLAYERS: u8 = 6;
+    pub const MIN_PLAYERS: u8 = 2;
+    pub const MAX_PLAYERS: u8 = 10;
+    /// how many consecutive stalemated turns end the game as a mutual loss
+    const STALEMATE_TURN_LIMIT: u64 = 3;
 
     pub fn new(num_players: u8) -> Result<Self, &'static str> {
         if num_players > Self::MAX_PLAYERS {
@@ -234,7 +291,7 @@ impl GameState {
         }
 
         let mut id_generator = IdGenerator::default();
-        let mut celestials = HashMap::new();
+        let mut celestials = BTreeMap::new();
 
         // generate non-asteroid celestial bodies
         let sol = CelestialBody::new(
@@ -248,7 +305,7 @@ impl GameState {
         // setup Earth bases
 
         // generate asteroids
-        let mut asteroids = HashMap::new();
+        let mut asteroids = BTreeMap::new();
         // TODO
 
         Ok(GameState {
@@ -266,10 +323,12 @@ impl GameState {
                 phase: TurnPhase::Economic,
             },
             id_generator,
-            stacks: HashMap::default(),
-            ordnance: HashMap::default(),
+            stacks: BTreeMap::default(),
+            ordnance: BTreeMap::default(),
             celestials,
             asteroids,
+            stalemate_turns: 0,
+            phase_timestamps: Vec::new(),
         })
     }
 
@@ -277,15 +336,72 @@ impl GameState {
         self.players.len() as u8
     }
 
+    pub fn player_ids(&self) -> impl Iterator<Item = Owner> + '_ {
+        self.players.keys().copied()
+    }
+
+    /// Human-readable description of the turn and the players
+    pub fn summary(&self) -> String {
+        let mut summary = format!("turn {}, {} phase", self.turn.number, self.turn.phase);
+        for (owner, username) in self.players.iter() {
+            summary += &format!(
+                "\nplayer {owner}: {}",
+                username.as_deref().unwrap_or("(unassigned)")
+            );
+        }
+        if let (Some(first), Some(last)) =
+            (self.phase_timestamps.first(), self.phase_timestamps.last())
+        {
+            if self.phase_timestamps.len() > 1 {
+                summary += &format!(
+                    "\naverage phase length: {} seconds",
+                    last.resolved_at.saturating_sub(first.resolved_at)
+                        / (self.phase_timestamps.len() as u64 - 1)
+                );
+            }
+        }
+        summary
+    }
+
+    /// Are orders computed against this turn and phase still applicable?
+    pub fn is_current_turn(&self, number: u64, phase: &TurnPhase) -> bool {
+        self.turn.number == number && self.turn.phase == *phase
+    }
+
     pub fn load_from_file(filename: &str) -> Result<Self, &'static str> {
         if let Ok(file) = fs::read_to_string(filename) {
-            serde_json::from_str(&file).map_err(|_| "could not parse save file")
+            let mut state: Self =
+                serde_json::from_str(&file).map_err(|_| "could not parse save file")?;
+            state.fast_forward_id_generator();
+            Ok(state)
         } else {
             Err("could not read file")
         }
     }
 
-    pub fn save_to_file(&self, filename: &str) {
+    /// Make sure the id generator won't hand out ids already in use (e.g. in a hand-edited save)
+    fn fast_forward_id_generator(&mut self) {
+        let max_id = self
+            .stacks
+            .values()
+            .flat_map(|stack| stack.ids())
+            .chain(self.ordnance.keys().copied())
+            .chain(self.celestials.keys().copied())
+            .chain(self.asteroids.keys().copied())
+            .map(|id| id.0)
+            .max();
+        if let Some(max_id) = max_id {
+            if self.id_generator.next <= max_id {
+                eprintln!(
+                    "warning: save file's next id is already in use - skipping ahead to {}",
+                    max_id + 1
+                );
+                self.id_generator.next = max_id + 1;
+            }
+        }
+    }
+
+    pub fn save_to_file(&self, filename: &str) -> Result<(), &'static str> {
         fn display_warning(filename: &str) {
             eprintln!("warning: unable to write to {filename} - your game will not be saved");
             eprintln!("warning: stopping the server is strongly recommended");
@@ -297,13 +413,15 @@ impl GameState {
                 stringified
             } else {
                 display_warning(filename);
-                return;
+                return Err("could not serialize game state");
             },
         )
         .is_err()
         {
             display_warning(filename);
+            return Err("could not write save file");
         }
+        Ok(())
     }
 
     /// Returns None if game is full
@@ -344,6 +462,10 @@ impl GameState {
             }
         }
 
+        if self.stalemate_turns >= Self::STALEMATE_TURN_LIMIT {
+            return SerializedState::MutualLoss;
+        }
+
         todo!();
     }
 
@@ -380,14 +502,14 @@ impl GameState {
         );
     }
 
-    fn process_economic_orders(&mut self, orders: &HashMap<Owner, Vec<Order>>) {
-        let mut foreign_cargo_deltas: HashMap<Owner, HashMap<(Id, Id), InventoryList>> =
-            HashMap::new();
+    fn process_economic_orders(&mut self, orders: &BTreeMap<Owner, Vec<Order>>) {
+        let mut foreign_cargo_deltas: BTreeMap<Owner, BTreeMap<(Id, Id), InventoryList>> =
+            BTreeMap::new();
         let mut repaired_habitats: HashSet<Id> = HashSet::new();
 
         // run orders
         for (owner, orders) in orders.iter() {
-            let mut new_stacks: HashMap<u64, Id> = HashMap::new();
+            let mut new_stacks: BTreeMap<u64, Id> = BTreeMap::new();
 
             for order in orders.iter() {
                 match order {
@@ -422,8 +544,9 @@ impl GameState {
                         if let Some(ordnance) = self.ordnance.get(&order.ordnance) {
                             if ordnance.owner != *owner {
                                 eprintln!(
-                                    "warning: invalid abort order from {} - invalid owner",
-                                    self.owner_to_username(*owner)
+                                    "warning: invalid abort order from {} - invalid owner for ordnance {}",
+                                    self.owner_to_username(*owner),
+                                    order.ordnance.short()
                                 );
                                 continue;
                             }
@@ -433,8 +556,9 @@ impl GameState {
                                 .expect("previously seen ordnance should still be in map");
                         } else {
                             eprintln!(
-                                "warning: invalid abort order from {} - invalid ordnance",
-                                self.owner_to_username(*owner)
+                                "warning: invalid abort order from {} - invalid ordnance {}",
+                                self.owner_to_username(*owner),
+                                order.ordnance.short()
                             );
                             continue;
                         }
@@ -469,7 +593,7 @@ impl GameState {
         }
     }
 
-    fn process_ordnance_orders(&mut self, orders: &HashMap<Owner, Vec<Order>>) {
+    fn process_ordnance_orders(&mut self, orders: &BTreeMap<Owner, Vec<Order>>) {
         for (owner, orders) in orders.iter() {
             for order in orders.iter() {
                 match order {
@@ -478,7 +602,7 @@ impl GameState {
                         if let Some(stack) = self.get_stack_with_owner_mut(order.stack, *owner) {
                             if let Some(clamp) = stack.launch_clamps.get_mut(&order.launch_clamp) {
                                 if clamp.damaged {
-                                    eprintln!("warning: invalid launch order from {} - damaged launch clamp", self.owner_to_username(*owner));
+                                    eprintln!("warning: invalid launch order from {} - damaged launch clamp {}", self.owner_to_username(*owner), order.launch_clamp.short());
                                     continue;
                                 }
 
@@ -500,28 +624,34 @@ impl GameState {
                                                 stack_position,
                                                 &stack_velocity + &order.boost,
                                             );
+                                            debug_assert!(
+                                                !self.ordnance.contains_key(&ordnance.id),
+                                                "freshly generated id should not be in use"
+                                            );
                                             self.ordnance.insert(ordnance.id, ordnance);
                                         } else {
-                                            eprintln!("warning: invalid launch order from {} - too large of a launch boost", self.owner_to_username(*owner));
+                                            eprintln!("warning: invalid launch order from {} - too large of a launch boost from launch clamp {}", self.owner_to_username(*owner), order.launch_clamp.short());
                                             continue;
                                         }
                                     }
                                     None => {
-                                        eprintln!("warning: invalid launch order from {} - unloaded launch clamp", self.owner_to_username(*owner));
+                                        eprintln!("warning: invalid launch order from {} - unloaded launch clamp {}", self.owner_to_username(*owner), order.launch_clamp.short());
                                         continue;
                                     }
                                 }
                             } else {
                                 eprintln!(
-                                    "warning: invalid launch order from {} - invalid launch clamp",
-                                    self.owner_to_username(*owner)
+                                    "warning: invalid launch order from {} - invalid launch clamp {}",
+                                    self.owner_to_username(*owner),
+                                    order.launch_clamp.short()
                                 );
                                 continue;
                             }
                         } else {
                             eprintln!(
-                                "warning: invalid launch order from {} - invalid launching stack",
-                                self.owner_to_username(*owner)
+                                "warning: invalid launch order from {} - invalid launching stack {}",
+                                self.owner_to_username(*owner),
+                                order.stack.short()
                             );
                             continue;
                         }
@@ -535,7 +665,12 @@ impl GameState {
         }
     }
 
-    fn shot_hit_check<T: Positionable>(&self, shooter: &Stack, target: &T) -> bool {
+    fn shot_hit_check<T: Positionable, R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+        shooter: &Stack,
+        target: &T,
+    ) -> bool {
         if self.celestials.iter().any(|(_, celestial)| {
             intercept_static(
                 shooter.position.cartesian(),
@@ -550,17 +685,17 @@ impl GameState {
 
         let range = (shooter.get_position() - target.get_position()).norm();
         let hit_chance = 0.5_f64.powi(range.try_into().expect("range shouldn't be too large"));
-        thread_rng().gen_bool(hit_chance)
+        rng.gen_bool(hit_chance)
     }
 
-    fn apply_damage(&mut self, stack: Id, amount: u64) {
+    fn apply_damage<R: Rng + ?Sized>(&mut self, rng: &mut R, stack: Id, amount: u64) {
         for _ in 0..amount {
             let stack = self
                 .stacks
                 .get_mut(&stack)
                 .expect("given stack should still be in map");
             for _ in 0..amount {
-                let component = stack.get_random_component();
+                let component = stack.get_random_component(rng);
                 if component.damage() {
                     let id = component.get_id();
                     stack
@@ -579,8 +714,12 @@ impl GameState {
         }
     }
 
-    fn process_combat_orders(&mut self, orders: &HashMap<Owner, Vec<Order>>) {
-        let mut pending_damage: HashMap<Id, u64> = HashMap::new();
+    fn process_combat_orders<R: Rng + ?Sized>(
+        &mut self,
+        rng: &mut R,
+        orders: &BTreeMap<Owner, Vec<Order>>,
+    ) {
+        let mut pending_damage: BTreeMap<Id, u64> = BTreeMap::new();
         let mut shot_guns: HashSet<Id> = HashSet::new();
 
         // generate pending damage values
@@ -594,44 +733,48 @@ impl GameState {
                                 if let Some(target) = self.stacks.get(&order.target) {
                                     if gun.damaged {
                                         eprintln!(
-                                            "warning: invalid shoot order from {} - damaged gun",
-                                            self.owner_to_username(*owner)
+                                            "warning: invalid shoot order from {} - damaged gun {}",
+                                            self.owner_to_username(*owner),
+                                            order.gun.short()
                                         );
                                         continue;
                                     } else if !shot_guns.insert(gun.id) {
-                                        eprintln!("warning: invalid shoot order from {} - gun already shot this turn", self.owner_to_username(*owner));
+                                        eprintln!("warning: invalid shoot order from {} - gun {} already shot this turn", self.owner_to_username(*owner), order.gun.short());
                                         continue;
                                     }
 
-                                    if self.shot_hit_check(shooter, target) {
+                                    if self.shot_hit_check(rng, shooter, target) {
                                         *(pending_damage.entry(target.id).or_insert(0)) += 1;
                                     }
                                 } else if let Some(target) = self.ordnance.get(&order.target) {
                                     if !shot_guns.insert(gun.id) {
-                                        eprintln!("warning: invalid shoot order from {} - gun already shot this turn", self.owner_to_username(*owner));
+                                        eprintln!("warning: invalid shoot order from {} - gun {} already shot this turn", self.owner_to_username(*owner), order.gun.short());
                                         continue;
                                     }
 
-                                    if self.shot_hit_check(shooter, target) {
+                                    if self.shot_hit_check(rng, shooter, target) {
                                         self.ordnance.remove(&order.target);
                                     }
                                 } else {
                                     eprintln!(
-                                        "warning: invalid shoot order from {} - invalid target",
-                                        self.owner_to_username(*owner)
+                                        "warning: invalid shoot order from {} - invalid target {}",
+                                        self.owner_to_username(*owner),
+                                        order.target.short()
                                     );
                                 }
                             } else {
                                 eprintln!(
-                                    "warning: invalid shoot order from {} - invalid gun",
-                                    self.owner_to_username(*owner)
+                                    "warning: invalid shoot order from {} - invalid gun {}",
+                                    self.owner_to_username(*owner),
+                                    order.gun.short()
                                 );
                                 continue;
                             }
                         } else {
                             eprintln!(
-                                "warning: invalid shoot order from {} - invalid shooting stack",
-                                self.owner_to_username(*owner)
+                                "warning: invalid shoot order from {} - invalid shooting stack {}",
+                                self.owner_to_username(*owner),
+                                order.shooter.short()
                             );
                             continue;
                         }
@@ -646,16 +789,15 @@ impl GameState {
 
         // apply the damage
         for (stack, amount) in pending_damage.iter() {
-            self.apply_damage(*stack, *amount);
+            self.apply_damage(rng, *stack, *amount);
         }
     }
 
     const HIT_CHECK_EPSILON: f64 = 1e-9;
 
-    fn process_movement_orders(&mut self, orders: &HashMap<Owner, Vec<Order>>) {
+    fn apply_burns(&mut self, orders: &BTreeMap<Owner, Vec<Order>>) {
         let mut burned_engines: HashSet<Id> = HashSet::new();
 
-        // burn
         for (owner, orders) in orders.iter() {
             for order in orders.iter() {
                 match order {
@@ -666,8 +808,9 @@ impl GameState {
                             if let Some(engine) = stack.engines.get_mut(&order.engine) {
                                 if engine.damaged {
                                     eprintln!(
-                                        "warning: invalid burn order from {} - damaged engine",
-                                        self.owner_to_username(*owner)
+                                        "warning: invalid burn order from {} - damaged engine {}",
+                                        self.owner_to_username(*owner),
+                                        order.engine.short()
                                     );
                                     continue;
                                 }
@@ -675,36 +818,36 @@ impl GameState {
                                 if let Some(fuel_tank) = stack.fuel_tanks.get_mut(&order.fuel_tank)
                                 {
                                     if fuel_tank.damaged {
-                                        eprintln!("warning: invalid burn order from {} - damaged fuel tank", self.owner_to_username(*owner));
+                                        eprintln!("warning: invalid burn order from {} - damaged fuel tank {}", self.owner_to_username(*owner), order.fuel_tank.short());
                                         continue;
                                     }
 
                                     match order.direction.norm() {
                                         1 => {
                                             if fuel_tank.fuel < 1 {
-                                                eprintln!("warning: invalid burn order from {} - out of fuel", self.owner_to_username(*owner));
+                                                eprintln!("warning: invalid burn order from {} - fuel tank {} out of fuel", self.owner_to_username(*owner), order.fuel_tank.short());
                                                 continue;
                                             }
 
                                             if !burned_engines.insert(engine.id) {
-                                                eprintln!("warning: invalid burn order from {} - engine already burned this turn", self.owner_to_username(*owner));
+                                                eprintln!("warning: invalid burn order from {} - engine {} already burned this turn", self.owner_to_username(*owner), order.engine.short());
                                                 continue;
                                             }
                                             fuel_tank.fuel -= 1;
                                         }
                                         2 => {
                                             if fuel_tank.fuel < 2 {
-                                                eprintln!("warning: invalid burn order from {} - out of fuel", self.owner_to_username(*owner));
+                                                eprintln!("warning: invalid burn order from {} - fuel tank {} out of fuel", self.owner_to_username(*owner), order.fuel_tank.short());
                                                 continue;
                                             }
 
                                             if engine.overload_state.unwrap_or(false) {
-                                                eprintln!("warning: invalid burn order from {} - engine can't overload", self.owner_to_username(*owner));
+                                                eprintln!("warning: invalid burn order from {} - engine {} can't overload", self.owner_to_username(*owner), order.engine.short());
                                                 continue;
                                             }
 
                                             if !burned_engines.insert(engine.id) {
-                                                eprintln!("warning: invalid burn order from {} - engine already burned this turn", self.owner_to_username(*owner));
+                                                eprintln!("warning: invalid burn order from {} - engine {} already burned this turn", self.owner_to_username(*owner), order.engine.short());
                                                 continue;
                                             }
                                             fuel_tank.fuel -= 2;
@@ -713,8 +856,9 @@ impl GameState {
                                         }
                                         _ => {
                                             eprintln!(
-                                            "warning: invalid burn order from {} - invalid delta-v",
-                                            self.owner_to_username(*owner)
+                                            "warning: invalid burn order from {} - invalid delta-v for stack {}",
+                                            self.owner_to_username(*owner),
+                                            order.stack.short()
                                         );
                                             continue;
                                         }
@@ -723,22 +867,25 @@ impl GameState {
                                     stack.velocity += &order.direction;
                                 } else {
                                     eprintln!(
-                                        "warning: invalid burn order from {} - invalid fuel tank",
-                                        self.owner_to_username(*owner)
+                                        "warning: invalid burn order from {} - invalid fuel tank {}",
+                                        self.owner_to_username(*owner),
+                                        order.fuel_tank.short()
                                     );
                                     continue;
                                 }
                             } else {
                                 eprintln!(
-                                    "warning: invalid shoot order from {} - invalid engine",
-                                    self.owner_to_username(*owner)
+                                    "warning: invalid burn order from {} - invalid engine {}",
+                                    self.owner_to_username(*owner),
+                                    order.engine.short()
                                 );
                                 continue;
                             }
                         } else {
                             eprintln!(
-                                "warning: invalid burn order from {} - invalid burning stack",
-                                self.owner_to_username(*owner)
+                                "warning: invalid burn order from {} - invalid burning stack {}",
+                                self.owner_to_username(*owner),
+                                order.stack.short()
                             );
                             continue;
                         }
@@ -750,8 +897,12 @@ impl GameState {
                 }
             }
         }
+    }
 
-        // ordnance hit check
+    /// Removes ordnance that hits a celestial body or a stack this turn, and applies its effects
+    ///
+    /// Ordnance whose targets were all destroyed by earlier detonations this turn isn't used up
+    fn resolve_detonations<R: Rng + ?Sized>(&mut self, rng: &mut R) {
         let mut to_remove = Vec::new();
         let mut hit_records = Vec::new();
         for (ordnance_id, ordnance) in self.ordnance.iter() {
@@ -775,8 +926,7 @@ impl GameState {
                 }
             }
 
-            // check to see if it hits a stack first
-            let mut stack_hit_distance: Option<f64> = None;
+            // every stack it passes before any celestial body could be hit, depending on what survives
             let mut stacks_hit = Vec::new();
             for (stack_id, stack) in self.stacks.iter() {
                 // no friendly fire
@@ -784,71 +934,101 @@ impl GameState {
                     continue;
                 }
 
-                let candidate_hit_distance = intercept_dynamic(
+                if let Some(hit_distance) = intercept_dynamic(
                     ordnance_start,
                     ordnance_end,
                     stack.position.cartesian(),
                     (&stack.position + &stack.velocity).cartesian(),
                     3.0_f64.sqrt() * 2.0,
-                );
-                match candidate_hit_distance {
-                    Some(candidate_hit_distance_value)
-                        if celestial_impact.is_none()
-                            || celestial_impact.is_some_and(|celestial_impact| {
-                                candidate_hit_distance_value <= celestial_impact
-                            }) =>
+                ) {
+                    if celestial_impact.is_none()
+                        || celestial_impact
+                            .is_some_and(|celestial_impact| hit_distance <= celestial_impact)
                     {
-                        match stack_hit_distance {
-                            Some(stack_hit_distance_value)
-                                if (stack_hit_distance_value - candidate_hit_distance_value)
-                                    .abs()
-                                    < Self::HIT_CHECK_EPSILON =>
-                            {
-                                stacks_hit.push(*stack_id);
-                            }
-                            Some(stack_hit_distance_value)
-                                if stack_hit_distance_value > candidate_hit_distance_value =>
-                            {
-                                stack_hit_distance = candidate_hit_distance;
-                                stacks_hit = vec![*stack_id];
-                            }
-                            None => {
-                                stack_hit_distance = candidate_hit_distance;
-                                stacks_hit = vec![*stack_id];
-                            }
-                            Some(_) => continue,
-                        }
+                        stacks_hit.push((hit_distance, *stack_id));
                     }
-                    _ => continue,
                 }
             }
 
-            // apply hits, if relevant
-            if celestial_impact.is_some() || stack_hit_distance.is_some() {
+            // ordnance that hits a stack is resolved once earlier detonations are known
+            if !stacks_hit.is_empty() {
+                hit_records.push((
+                    *ordnance_id,
+                    stacks_hit,
+                    celestial_impact.is_some(),
+                    ordnance.ordnance_type,
+                ));
+            } else if celestial_impact.is_some() {
                 to_remove.push(*ordnance_id);
             }
-            if let Some(hit) = stacks_hit.choose(&mut thread_rng()) {
-                hit_records.push((*hit, ordnance.ordnance_type));
-            }
         }
-        // apply hits
         for id in to_remove.iter() {
             self.ordnance
                 .remove(id)
                 .expect("previously seen ordnance should still be in map");
         }
-        for (hit, ordnance_type) in hit_records.iter() {
-            // stack may have been previously destroyed - punt
-            if !self.stacks.contains_key(hit) {
-                continue;
+
+        fn first_hit_distance(stacks_hit: &[(f64, Id)]) -> Option<f64> {
+            stacks_hit
+                .iter()
+                .map(|(hit_distance, _)| *hit_distance)
+                .min_by(f64::total_cmp)
+        }
+
+        // apply hits in the order they happen - a destroyed target pushes back the hit of any
+        // ordnance that was going to hit it, so the order is re-checked after every detonation
+        while !hit_records.is_empty() {
+            // targets may have been destroyed by earlier detonations
+            for (_, stacks_hit, ..) in hit_records.iter_mut() {
+                stacks_hit.retain(|(_, stack)| self.stacks.contains_key(stack));
+            }
+            for (ordnance_id, stacks_hit, hits_celestial, _) in hit_records.iter() {
+                // nothing left to hit - keep going, unless a celestial body is in the way
+                if stacks_hit.is_empty() && *hits_celestial {
+                    self.ordnance
+                        .remove(ordnance_id)
+                        .expect("previously seen ordnance should still be in map");
+                }
             }
+            hit_records.retain(|(_, stacks_hit, ..)| !stacks_hit.is_empty());
 
+            let next_hit = match hit_records
+                .iter()
+                .enumerate()
+                .filter_map(|(index, (_, stacks_hit, ..))| {
+                    first_hit_distance(stacks_hit).map(|hit_distance| (index, hit_distance))
+                })
+                .min_by(|(_, first), (_, second)| first.total_cmp(second))
+            {
+                Some((index, _)) => index,
+                None => break,
+            };
+            let (ordnance_id, stacks_hit, _, ordnance_type) = hit_records.remove(next_hit);
+
+            // several stacks may be hit at the same moment - pick one of them
+            let closest_distance =
+                first_hit_distance(&stacks_hit).expect("hit records should have a target left");
+            let closest_stacks: Vec<Id> = stacks_hit
+                .iter()
+                .filter(|(hit_distance, _)| {
+                    (hit_distance - closest_distance).abs() < Self::HIT_CHECK_EPSILON
+                })
+                .map(|(_, stack)| *stack)
+                .collect();
+            let hit = *closest_stacks
+                .choose(rng)
+                .expect("closest stacks should include the first hit");
+
+            self.ordnance
+                .remove(&ordnance_id)
+                .expect("previously seen ordnance should still be in map");
             match ordnance_type {
                 stack::OrdnanceType::Mine | stack::OrdnanceType::Torpedo => self.apply_damage(
-                    *hit,
+                    rng,
+                    hit,
                     (self
                         .stacks
-                        .get(hit)
+                        .get(&hit)
                         .expect("previously seen stack should still be in map")
                         .num_components() as f64
                         * ordnance_type.damage_fraction())
@@ -856,20 +1036,17 @@ impl GameState {
                 ),
                 stack::OrdnanceType::Nuke => {
                     self.stacks
-                        .remove(hit)
+                        .remove(&hit)
                         .expect("previously seen stack should still be in map");
                 }
             }
         }
+    }
 
-        // tick movement and miners
-        // TODO: gravity
-        for (_, ordnance) in self.ordnance.iter_mut() {
-            // note: celestial body impact check already done above
-            ordnance.position += &ordnance.velocity;
-        }
+    /// Removes stacks whose trajectory this turn intersects a celestial body
+    fn resolve_crashes(&mut self) {
         let mut to_remove = Vec::new();
-        for (id, stack) in self.stacks.iter_mut() {
+        for (id, stack) in self.stacks.iter() {
             if self.celestials.iter().any(|(_, celestial)| {
                 intercept_static(
                     stack.position.cartesian(),
@@ -880,12 +1057,29 @@ impl GameState {
                 .is_some()
             }) {
                 to_remove.push(*id);
-                continue;
             }
+        }
+        for id in to_remove.iter() {
+            self.stacks
+                .remove(id)
+                .expect("previously seen stack should still be in map");
+        }
+    }
 
+    fn integrate_movement(&mut self) {
+        // TODO: gravity
+        for (_, ordnance) in self.ordnance.iter_mut() {
+            // note: celestial body impact check already done in resolve_detonations
+            ordnance.position += &ordnance.velocity;
+        }
+        for (_, stack) in self.stacks.iter_mut() {
+            // note: celestial body impact check already done in resolve_crashes
             stack.position += &stack.velocity;
+        }
+    }
 
-            // miner tick
+    fn tick_miners(&mut self) {
+        for (_, stack) in self.stacks.iter_mut() {
             if stack.velocity.is_zero() && !stack.miners.is_empty() {
                 if let Some((_, asteroids)) = self
                     .asteroids
@@ -898,20 +1092,640 @@ impl GameState {
                 }
             }
         }
-        for id in to_remove.iter() {
-            self.stacks
-                .remove(id)
-                .expect("previously seen stack should still be in map");
-        }
     }
 
-    pub fn process_orders(&mut self, orders: &HashMap<Owner, Vec<Order>>) {
+    fn process_movement_orders<R: Rng + ?Sized>(
+        &mut self,
+        rng: &mut R,
+        orders: &BTreeMap<Owner, Vec<Order>>,
+    ) {
+        self.apply_burns(orders);
+        self.resolve_detonations(rng);
+        self.resolve_crashes();
+        self.integrate_movement();
+        self.tick_miners();
+    }
+
+    /// Is nothing left in the game that could change the state?
+    ///
+    /// Ordnance in flight always counts as activity; otherwise defers to Stack::can_act
+    pub fn is_stalemate(&self) -> bool {
+        self.ordnance.is_empty() && self.stacks.iter().all(|(_, stack)| !stack.can_act())
+    }
+
+    pub fn process_orders(&mut self, orders: &BTreeMap<Owner, Vec<Order>>) {
+        self.process_orders_with_rng(&mut thread_rng(), orders);
+    }
+
+    /// Process orders, drawing all random outcomes (hits, damage) from the given rng
+    pub fn process_orders_with_rng<R: Rng + ?Sized>(
+        &mut self,
+        rng: &mut R,
+        orders: &BTreeMap<Owner, Vec<Order>>,
+    ) {
         match self.turn.phase {
             TurnPhase::Economic => self.process_economic_orders(orders),
             TurnPhase::Ordnance => self.process_ordnance_orders(orders),
-            TurnPhase::Combat => self.process_combat_orders(orders),
-            TurnPhase::Movement => self.process_movement_orders(orders),
+            TurnPhase::Combat => self.process_combat_orders(rng, orders),
+            TurnPhase::Movement => {
+                self.process_movement_orders(rng, orders);
+                if self.is_stalemate() {
+                    self.stalemate_turns += 1;
+                } else {
+                    self.stalemate_turns = 0;
+                }
+            }
         }
+        self.phase_timestamps.push(PhaseTimestamp {
+            number: self.turn.number,
+            phase: self.turn.phase,
+            resolved_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since_epoch| since_epoch.as_secs()),
+        });
         self.turn.next();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{
+        game::order::{parse_orders_header, Burn},
+        vec2::AxialDisplacement,
+    };
+
+    use super::{
+        stack::{Engine, FuelTank, OrdnanceType},
+        *,
+    };
+
+    fn empty_state() -> GameState {
+        GameState {
+            players: [
+                (Owner(0), Some("alice".to_owned())),
+                (Owner(1), Some("bob".to_owned())),
+            ]
+            .into_iter()
+            .collect(),
+            turn: Turn {
+                number: 0,
+                phase: TurnPhase::Movement,
+            },
+            id_generator: IdGenerator::default(),
+            stacks: BTreeMap::new(),
+            ordnance: BTreeMap::new(),
+            celestials: BTreeMap::new(),
+            asteroids: BTreeMap::new(),
+            stalemate_turns: 0,
+            phase_timestamps: Vec::new(),
+        }
+    }
+
+    fn add_stack(
+        state: &mut GameState,
+        owner: Owner,
+        position: AxialPosition,
+        velocity: AxialDisplacement,
+    ) -> Id {
+        let id = state.id_generator.generate();
+        state.stacks.insert(
+            id,
+            Stack {
+                id,
+                owner,
+                name: "test stack".to_owned(),
+                position,
+                velocity,
+                fuel_tanks: BTreeMap::new(),
+                cargo_holds: BTreeMap::new(),
+                engines: BTreeMap::new(),
+                guns: BTreeMap::new(),
+                launch_clamps: BTreeMap::new(),
+                habitats: BTreeMap::new(),
+                miners: BTreeMap::new(),
+                factories: BTreeMap::new(),
+                armour_plates: BTreeMap::new(),
+            },
+        );
+        id
+    }
+
+    fn add_ordnance(
+        state: &mut GameState,
+        owner: Owner,
+        ordnance_type: OrdnanceType,
+        position: AxialPosition,
+        velocity: AxialDisplacement,
+    ) -> Id {
+        let ordnance = Ordnance::new(
+            &mut state.id_generator,
+            owner,
+            ordnance_type,
+            position,
+            velocity,
+        );
+        let id = ordnance.id;
+        state.ordnance.insert(id, ordnance);
+        id
+    }
+
+    fn add_celestial(state: &mut GameState, position: AxialPosition) -> Id {
+        let celestial =
+            CelestialBody::new(&mut state.id_generator, position, "#ffff00".to_owned(), 0.8);
+        let id = celestial.id;
+        state.celestials.insert(id, celestial);
+        id
+    }
+
+    fn dead_state() -> GameState {
+        let mut state = empty_state();
+        state.turn.phase = TurnPhase::Economic;
+        for owner in [Owner(0), Owner(1)] {
+            add_stack(
+                &mut state,
+                owner,
+                AxialPosition::new(owner.0 as i64 * 10, 0),
+                AxialDisplacement::new(0, 0),
+            );
+        }
+        state
+    }
+
+    fn resolve_turn(state: &mut GameState) {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..4 {
+            state.process_orders_with_rng(&mut rng, &BTreeMap::new());
+        }
+    }
+
+    #[test]
+    fn ordnance_in_flight_prevents_stalemate() {
+        let mut state = dead_state();
+        assert!(state.is_stalemate());
+
+        add_ordnance(
+            &mut state,
+            Owner(0),
+            OrdnanceType::Mine,
+            AxialPosition::new(0, 5),
+            AxialDisplacement::new(0, 0),
+        );
+        assert!(!state.is_stalemate());
+    }
+
+    #[test]
+    fn stalemate_turns_count_full_turns_and_reset_on_activity() {
+        let mut state = dead_state();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        // only the end of the movement phase counts
+        for _ in 0..3 {
+            state.process_orders_with_rng(&mut rng, &BTreeMap::new());
+            assert_eq!(state.stalemate_turns, 0);
+        }
+        state.process_orders_with_rng(&mut rng, &BTreeMap::new());
+        assert_eq!(state.stalemate_turns, 1);
+
+        resolve_turn(&mut state);
+        assert_eq!(state.stalemate_turns, 2);
+
+        let stack = *state.stacks.keys().next().unwrap();
+        state.stacks.get_mut(&stack).unwrap().velocity = AxialDisplacement::new(1, 0);
+        resolve_turn(&mut state);
+        assert_eq!(state.stalemate_turns, 0);
+    }
+
+    #[test]
+    fn stalemated_game_ends_in_mutual_loss() {
+        let mut state = dead_state();
+        for _ in 0..GameState::STALEMATE_TURN_LIMIT {
+            resolve_turn(&mut state);
+        }
+
+        assert!(matches!(
+            state.serialize_for_player(Owner(0)),
+            SerializedState::MutualLoss
+        ));
+    }
+
+    fn timestamp(number: u64, phase: TurnPhase, resolved_at: u64) -> PhaseTimestamp {
+        PhaseTimestamp {
+            number,
+            phase,
+            resolved_at,
+        }
+    }
+
+    #[test]
+    fn summary_omits_average_phase_length_until_two_phases_resolved() {
+        let mut state = empty_state();
+        assert!(!state.summary().contains("average phase length"));
+
+        state
+            .phase_timestamps
+            .push(timestamp(0, TurnPhase::Economic, 1000));
+        assert!(!state.summary().contains("average phase length"));
+    }
+
+    #[test]
+    fn summary_averages_time_between_resolutions() {
+        let mut state = empty_state();
+        state.phase_timestamps = vec![
+            timestamp(0, TurnPhase::Economic, 1000),
+            timestamp(0, TurnPhase::Ordnance, 1030),
+            timestamp(0, TurnPhase::Combat, 1040),
+            timestamp(0, TurnPhase::Movement, 1090),
+        ];
+
+        assert_eq!(
+            state.summary(),
+            "turn 0, movement phase\nplayer 0: alice\nplayer 1: bob\naverage phase length: 30 seconds"
+        );
+    }
+
+    #[test]
+    fn phase_timestamps_survive_a_save() {
+        let mut state = empty_state();
+        state.phase_timestamps = vec![
+            timestamp(2, TurnPhase::Combat, 1000),
+            timestamp(2, TurnPhase::Movement, 1060),
+        ];
+
+        let loaded: GameState =
+            serde_json::from_str(&serde_json::to_string(&state).unwrap()).unwrap();
+
+        assert_eq!(loaded.phase_timestamps.len(), 2);
+        assert_eq!(loaded.phase_timestamps[1].number, 2);
+        assert!(loaded.phase_timestamps[1].phase == TurnPhase::Movement);
+        assert_eq!(loaded.phase_timestamps[1].resolved_at, 1060);
+    }
+
+    #[test]
+    fn saves_without_phase_timestamps_still_load() {
+        let mut save = serde_json::to_value(empty_state()).unwrap();
+        save.as_object_mut().unwrap().remove("phase_timestamps");
+
+        let loaded: GameState = serde_json::from_value(save).unwrap();
+
+        assert!(loaded.phase_timestamps.is_empty());
+    }
+
+    /// A small save using every kind of object, pinned so changes that break old saves get noticed
+    ///
+    /// If a format change is intended, regenerate it with
+    /// `SOLAR_DAWN_UPDATE_GOLDEN=1 cargo test golden_save` and review the diff
+    const GOLDEN_SAVE: &str = include_str!("state/golden_save.json");
+
+    #[test]
+    fn golden_save_round_trips_byte_for_byte() {
+        let state: GameState =
+            serde_json::from_str(GOLDEN_SAVE).expect("golden save should still load");
+        let reserialized = serde_json::to_string(&state).unwrap() + "\n";
+
+        if env::var_os("SOLAR_DAWN_UPDATE_GOLDEN").is_some() {
+            fs::write(
+                concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/src/game/state/golden_save.json"
+                ),
+                reserialized,
+            )
+            .unwrap();
+            return;
+        }
+        assert_eq!(reserialized, GOLDEN_SAVE);
+    }
+
+    fn temp_save(name: &str, contents: &str) -> String {
+        let filename = env::temp_dir()
+            .join(format!("solar_dawn_{name}_{}.json", std::process::id()))
+            .to_str()
+            .unwrap()
+            .to_owned();
+        fs::write(&filename, contents).unwrap();
+        filename
+    }
+
+    #[test]
+    fn valid_save_loads() {
+        let filename = temp_save("valid_save", GOLDEN_SAVE);
+        let loaded = GameState::load_from_file(&filename);
+        fs::remove_file(&filename).unwrap();
+
+        let loaded = loaded.expect("golden save should load");
+        assert!(loaded.is_current_turn(4, &TurnPhase::Combat));
+    }
+
+    #[test]
+    fn corrupted_save_is_rejected() {
+        let filename = temp_save("corrupted_save", &GOLDEN_SAVE[..GOLDEN_SAVE.len() / 2]);
+        let loaded = GameState::load_from_file(&filename);
+        fs::remove_file(&filename).unwrap();
+
+        assert!(loaded.is_err());
+        assert!(GameState::load_from_file(&filename).is_err());
+    }
+
+    #[test]
+    fn player_count_is_bounded() {
+        assert!(GameState::new(GameState::MIN_PLAYERS - 1).is_err());
+        assert!(GameState::new(2).is_ok());
+        assert_eq!(GameState::new(10).unwrap().num_players(), 10);
+        assert!(GameState::new(11).is_err());
+
+        // player ids are zero-based
+        assert!(Owner::try_from(9).is_ok());
+        assert!(Owner::try_from(10).is_err());
+    }
+
+    #[test]
+    fn short_ids_are_base_36() {
+        assert_eq!(Id(0).short(), "0");
+        assert_eq!(Id(9).short(), "9");
+        assert_eq!(Id(10).short(), "a");
+        assert_eq!(Id(35).short(), "z");
+        assert_eq!(Id(36).short(), "10");
+        assert_eq!(Id(1295).short(), "zz");
+        assert_eq!(Id(1296).short(), "100");
+        assert_eq!(Id(u64::MAX).short(), "3w5e11264sgsf");
+    }
+
+    #[test]
+    fn stale_id_generator_skips_past_ids_in_use() {
+        let mut state = empty_state();
+        add_celestial(&mut state, AxialPosition::new(0, 0));
+        let stack = add_stack(
+            &mut state,
+            Owner(0),
+            AxialPosition::new(5, 0),
+            AxialDisplacement::new(0, 0),
+        );
+        state.stacks.get_mut(&stack).unwrap().engines.insert(
+            Id(9),
+            Engine {
+                id: Id(9),
+                overload_state: None,
+                damaged: false,
+            },
+        );
+        // as if the save had been edited by hand
+        state.id_generator.next = 2;
+
+        let filename = temp_save(
+            "stale_id_generator",
+            &serde_json::to_string(&state).unwrap(),
+        );
+        let loaded = GameState::load_from_file(&filename);
+        fs::remove_file(&filename).unwrap();
+
+        assert_eq!(loaded.unwrap().id_generator.next, 10);
+    }
+
+    #[test]
+    fn up_to_date_id_generator_is_left_alone() {
+        let mut state = empty_state();
+        add_celestial(&mut state, AxialPosition::new(0, 0));
+        state.id_generator.next = 20;
+
+        state.fast_forward_id_generator();
+
+        assert_eq!(state.id_generator.next, 20);
+    }
+
+    #[test]
+    fn orders_for_an_earlier_turn_or_phase_are_stale() {
+        let mut state = empty_state();
+        state.turn = Turn {
+            number: 3,
+            phase: TurnPhase::Combat,
+        };
+
+        let (number, phase, _) = parse_orders_header("3\ncombat\n").unwrap();
+        assert!(state.is_current_turn(number, &phase));
+        let (number, phase, _) = parse_orders_header("3\nordnance\n").unwrap();
+        assert!(!state.is_current_turn(number, &phase));
+        let (number, phase, _) = parse_orders_header("2\ncombat\n").unwrap();
+        assert!(!state.is_current_turn(number, &phase));
+    }
+
+    #[test]
+    fn second_nuke_survives_when_its_only_target_is_destroyed() {
+        let mut state = empty_state();
+        let target = add_stack(
+            &mut state,
+            Owner(0),
+            AxialPosition::new(0, 0),
+            AxialDisplacement::new(0, 0),
+        );
+        for _ in 0..2 {
+            add_ordnance(
+                &mut state,
+                Owner(1),
+                OrdnanceType::Nuke,
+                AxialPosition::new(0, 0),
+                AxialDisplacement::new(10, 0),
+            );
+        }
+
+        state.resolve_detonations(&mut StdRng::seed_from_u64(0));
+
+        assert!(!state.stacks.contains_key(&target));
+        assert_eq!(state.ordnance.len(), 1);
+    }
+
+    #[test]
+    fn second_nuke_hits_the_next_stack_along_its_path() {
+        let mut state = empty_state();
+        let near = add_stack(
+            &mut state,
+            Owner(0),
+            AxialPosition::new(0, 0),
+            AxialDisplacement::new(0, 0),
+        );
+        let far = add_stack(
+            &mut state,
+            Owner(0),
+            AxialPosition::new(8, 0),
+            AxialDisplacement::new(0, 0),
+        );
+        for _ in 0..2 {
+            add_ordnance(
+                &mut state,
+                Owner(1),
+                OrdnanceType::Nuke,
+                AxialPosition::new(0, 0),
+                AxialDisplacement::new(10, 0),
+            );
+        }
+
+        state.resolve_detonations(&mut StdRng::seed_from_u64(0));
+
+        assert!(!state.stacks.contains_key(&near));
+        assert!(!state.stacks.contains_key(&far));
+        assert!(state.ordnance.is_empty());
+    }
+
+    #[test]
+    fn retargeted_nuke_waits_for_earlier_hits_on_its_new_target() {
+        let mut state = empty_state();
+        let x = add_stack(
+            &mut state,
+            Owner(0),
+            AxialPosition::new(3, 0),
+            AxialDisplacement::new(0, 0),
+        );
+        let y = add_stack(
+            &mut state,
+            Owner(0),
+            AxialPosition::new(10, 0),
+            AxialDisplacement::new(0, 0),
+        );
+        // passes x a tenth of the way along and y most of the way along
+        let slow = add_ordnance(
+            &mut state,
+            Owner(1),
+            OrdnanceType::Nuke,
+            AxialPosition::new(0, 0),
+            AxialDisplacement::new(10, 0),
+        );
+        // reaches x first
+        let fast = add_ordnance(
+            &mut state,
+            Owner(1),
+            OrdnanceType::Nuke,
+            AxialPosition::new(0, 0),
+            AxialDisplacement::new(20, 0),
+        );
+        // starts past x and reaches y before the slow nuke does
+        let late = add_ordnance(
+            &mut state,
+            Owner(1),
+            OrdnanceType::Nuke,
+            AxialPosition::new(6, 0),
+            AxialDisplacement::new(10, 0),
+        );
+
+        state.resolve_detonations(&mut StdRng::seed_from_u64(0));
+
+        assert!(!state.stacks.contains_key(&x));
+        assert!(!state.stacks.contains_key(&y));
+        assert!(!state.ordnance.contains_key(&fast));
+        assert!(!state.ordnance.contains_key(&late));
+        assert!(state.ordnance.contains_key(&slow));
+    }
+
+    #[test]
+    fn nukes_ignore_friendly_stacks_and_stop_at_celestial_bodies() {
+        let mut state = empty_state();
+        let friendly = add_stack(
+            &mut state,
+            Owner(1),
+            AxialPosition::new(0, 0),
+            AxialDisplacement::new(0, 0),
+        );
+        add_celestial(&mut state, AxialPosition::new(5, 0));
+        add_ordnance(
+            &mut state,
+            Owner(1),
+            OrdnanceType::Nuke,
+            AxialPosition::new(0, 0),
+            AxialDisplacement::new(10, 0),
+        );
+
+        state.resolve_detonations(&mut StdRng::seed_from_u64(0));
+
+        assert!(state.stacks.contains_key(&friendly));
+        assert!(state.ordnance.is_empty());
+    }
+
+    #[test]
+    fn stacks_crash_into_celestial_bodies_in_their_path() {
+        let mut state = empty_state();
+        add_celestial(&mut state, AxialPosition::new(5, 0));
+        let crashing = add_stack(
+            &mut state,
+            Owner(0),
+            AxialPosition::new(0, 0),
+            AxialDisplacement::new(10, 0),
+        );
+        let clear = add_stack(
+            &mut state,
+            Owner(0),
+            AxialPosition::new(0, 10),
+            AxialDisplacement::new(0, 0),
+        );
+
+        state.resolve_crashes();
+
+        assert!(!state.stacks.contains_key(&crashing));
+        assert!(state.stacks.contains_key(&clear));
+    }
+
+    #[test]
+    fn movement_applies_velocity() {
+        let mut state = empty_state();
+        let stack = add_stack(
+            &mut state,
+            Owner(0),
+            AxialPosition::new(1, 2),
+            AxialDisplacement::new(3, -1),
+        );
+        let ordnance = add_ordnance(
+            &mut state,
+            Owner(1),
+            OrdnanceType::Mine,
+            AxialPosition::new(0, 0),
+            AxialDisplacement::new(-2, 2),
+        );
+
+        state.integrate_movement();
+
+        assert!(state.stacks[&stack].position == AxialPosition::new(4, 1));
+        assert!(state.ordnance[&ordnance].position == AxialPosition::new(-2, 2));
+    }
+
+    #[test]
+    fn burns_spend_fuel_and_change_velocity() {
+        let mut state = empty_state();
+        let stack = add_stack(
+            &mut state,
+            Owner(0),
+            AxialPosition::new(0, 0),
+            AxialDisplacement::new(0, 0),
+        );
+        let engine = state.id_generator.generate();
+        let fuel_tank = state.id_generator.generate();
+        let burning_stack = state.stacks.get_mut(&stack).unwrap();
+        burning_stack.engines.insert(
+            engine,
+            Engine {
+                id: engine,
+                overload_state: None,
+                damaged: false,
+            },
+        );
+        burning_stack
+            .fuel_tanks
+            .insert(fuel_tank, FuelTank::new(fuel_tank, 2));
+
+        let burn = || {
+            Order::Burn(Burn {
+                stack,
+                engine,
+                fuel_tank,
+                direction: AxialDisplacement::new(1, 0),
+            })
+        };
+        // the second burn reuses the engine, so it's rejected
+        let orders = [(Owner(0), vec![burn(), burn()])].into_iter().collect();
+        state.apply_burns(&orders);
+
+        let burning_stack = &state.stacks[&stack];
+        assert!(burning_stack.velocity == AxialDisplacement::new(1, 0));
+        assert_eq!(burning_stack.fuel_tanks[&fuel_tank].fuel, 1);
+    }
+}
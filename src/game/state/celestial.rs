@@ -19,7 +19,7 @@
 
 use rand::{
     distributions::{Distribution, Standard},
-    thread_rng, Rng,
+    Rng,
 };
 use regex::Regex;
 use serde::{de::Error, Deserialize, Deserializer, Serialize};
@@ -62,11 +62,15 @@ pub struct AsteroidField {
     pub resource: AsteroidResource,
 }
 impl AsteroidField {
-    pub fn new(id_generator: &mut IdGenerator, position: AxialPosition) -> Self {
+    pub fn new<R: Rng + ?Sized>(
+        rng: &mut R,
+        id_generator: &mut IdGenerator,
+        position: AxialPosition,
+    ) -> Self {
         Self {
             id: id_generator.generate(),
             position,
-            resource: thread_rng().gen(),
+            resource: rng.gen(),
         }
     }
 }
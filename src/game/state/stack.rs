@@ -17,9 +17,9 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
-use rand::{thread_rng, Rng};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
 use crate::vec2::{AxialDisplacement, AxialPosition};
@@ -101,15 +101,15 @@ pub struct Stack {
     pub name: String,
     pub position: AxialPosition,
     pub velocity: AxialDisplacement,
-    pub fuel_tanks: HashMap<Id, FuelTank>,
-    pub cargo_holds: HashMap<Id, CargoHold>,
-    pub engines: HashMap<Id, Engine>,
-    pub guns: HashMap<Id, Gun>,
-    pub launch_clamps: HashMap<Id, LaunchClamp>,
-    pub habitats: HashMap<Id, Habitat>,
-    pub miners: HashMap<Id, Miner>,
-    pub factories: HashMap<Id, Factory>,
-    pub armour_plates: HashMap<Id, ArmourPlate>,
+    pub fuel_tanks: BTreeMap<Id, FuelTank>,
+    pub cargo_holds: BTreeMap<Id, CargoHold>,
+    pub engines: BTreeMap<Id, Engine>,
+    pub guns: BTreeMap<Id, Gun>,
+    pub launch_clamps: BTreeMap<Id, LaunchClamp>,
+    pub habitats: BTreeMap<Id, Habitat>,
+    pub miners: BTreeMap<Id, Miner>,
+    pub factories: BTreeMap<Id, Factory>,
+    pub armour_plates: BTreeMap<Id, ArmourPlate>,
 }
 impl Stack {
     pub fn num_components(&self) -> usize {
@@ -124,12 +124,12 @@ impl Stack {
             + self.armour_plates.len()
     }
 
-    pub fn get_random_component(&mut self) -> &mut dyn Component {
+    pub fn get_random_component<R: Rng + ?Sized>(&mut self, rng: &mut R) -> &mut dyn Component {
         let num_components = self.num_components();
         if num_components == 0 {
             panic!("should not have empty stack")
         }
-        let mut selected_component_index = thread_rng().gen_range(0..num_components);
+        let mut selected_component_index = rng.gen_range(0..num_components);
 
         if selected_component_index < self.cargo_holds.len() {
             return self
@@ -248,6 +248,70 @@ impl Stack {
             && self.armour_plates.is_empty()
     }
 
+    /// The stack's own id and the ids of all of its components
+    pub fn ids(&self) -> impl Iterator<Item = Id> + '_ {
+        std::iter::once(self.id)
+            .chain(self.fuel_tanks.keys().copied())
+            .chain(self.cargo_holds.keys().copied())
+            .chain(self.engines.keys().copied())
+            .chain(self.guns.keys().copied())
+            .chain(self.launch_clamps.keys().copied())
+            .chain(self.habitats.keys().copied())
+            .chain(self.miners.keys().copied())
+            .chain(self.factories.keys().copied())
+            .chain(self.armour_plates.keys().copied())
+    }
+
+    fn has_damaged_component(&self) -> bool {
+        self.fuel_tanks.values().any(|fuel_tank| fuel_tank.damaged)
+            || self
+                .cargo_holds
+                .values()
+                .any(|cargo_hold| cargo_hold.damaged)
+            || self.engines.values().any(|engine| engine.damaged)
+            || self.guns.values().any(|gun| gun.damaged)
+            || self
+                .launch_clamps
+                .values()
+                .any(|launch_clamp| launch_clamp.damaged)
+            || self.habitats.values().any(|habitat| habitat.damaged)
+            || self.miners.values().any(|miner| miner.damaged)
+            || self.factories.values().any(|factory| factory.damaged)
+            || self
+                .armour_plates
+                .values()
+                .any(|armour_plate| armour_plate.damaged)
+    }
+
+    /// Could this stack still change the game state on its own?
+    ///
+    /// Deliberately generous: a stack counts if it is moving, can burn, can fight (a gun or a loaded
+    /// launch clamp), can produce (a miner or a factory), or can repair (a habitat with materials on
+    /// hand and something damaged). Only intact components count.
+    pub fn can_act(&self) -> bool {
+        let can_burn = self.engines.values().any(|engine| !engine.damaged)
+            && self
+                .fuel_tanks
+                .values()
+                .any(|fuel_tank| !fuel_tank.damaged && fuel_tank.fuel > 0);
+        let can_fight = self.guns.values().any(|gun| !gun.damaged)
+            || self
+                .launch_clamps
+                .values()
+                .any(|launch_clamp| !launch_clamp.damaged && launch_clamp.load.is_some());
+        let can_produce = self.miners.values().any(|miner| !miner.damaged)
+            || self.factories.values().any(|factory| !factory.damaged);
+
+        let can_repair = self.habitats.values().any(|habitat| !habitat.damaged)
+            && self
+                .cargo_holds
+                .values()
+                .any(|cargo_hold| !cargo_hold.damaged && cargo_hold.inventory.materials > 0)
+            && self.has_damaged_component();
+
+        !self.velocity.is_zero() || can_burn || can_fight || can_produce || can_repair
+    }
+
     pub fn remove_component(&mut self, component: Id) -> Result<(), ()> {
         if self.fuel_tanks.remove(&component).is_some()
             || self.cargo_holds.remove(&component).is_some()
@@ -286,6 +350,16 @@ pub struct FuelTank {
     pub fuel: u64,
     pub damaged: bool,
 }
+#[cfg(test)]
+impl FuelTank {
+    pub fn new(id: Id, fuel: u64) -> Self {
+        Self {
+            id,
+            fuel,
+            damaged: false,
+        }
+    }
+}
 impl Component for FuelTank {
     fn damage(&mut self) -> bool {
         if !self.damaged {
@@ -510,3 +584,92 @@ impl IdAble for ArmourPlate {
         self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stationary_stack() -> Stack {
+        Stack {
+            id: Id(1),
+            owner: Owner(0),
+            name: "test stack".to_owned(),
+            position: AxialPosition::new(0, 0),
+            velocity: AxialDisplacement::new(0, 0),
+            fuel_tanks: BTreeMap::new(),
+            cargo_holds: BTreeMap::new(),
+            engines: BTreeMap::new(),
+            guns: BTreeMap::new(),
+            launch_clamps: BTreeMap::new(),
+            habitats: BTreeMap::new(),
+            miners: BTreeMap::new(),
+            factories: BTreeMap::new(),
+            armour_plates: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn stack_with_only_damaged_or_idle_components_cannot_act() {
+        let mut stack = stationary_stack();
+        stack.engines.insert(
+            Id(2),
+            Engine {
+                id: Id(2),
+                overload_state: None,
+                damaged: true,
+            },
+        );
+        stack.fuel_tanks.insert(
+            Id(3),
+            FuelTank {
+                id: Id(3),
+                fuel: 0,
+                damaged: false,
+            },
+        );
+        stack.habitats.insert(
+            Id(4),
+            Habitat {
+                id: Id(4),
+                owner: Owner(0),
+                damaged: false,
+            },
+        );
+
+        // the habitat could repair the engine, but there are no materials to do it with
+        assert!(!stack.can_act());
+    }
+
+    #[test]
+    fn habitat_with_materials_and_damaged_component_can_act() {
+        let mut stack = stationary_stack();
+        stack.armour_plates.insert(
+            Id(2),
+            ArmourPlate {
+                id: Id(2),
+                damaged: true,
+            },
+        );
+        stack.cargo_holds.insert(
+            Id(3),
+            CargoHold {
+                id: Id(3),
+                inventory: InventoryList::materials(1),
+                damaged: false,
+            },
+        );
+        stack.habitats.insert(
+            Id(4),
+            Habitat {
+                id: Id(4),
+                owner: Owner(0),
+                damaged: false,
+            },
+        );
+        assert!(stack.can_act());
+
+        // nothing left to repair
+        stack.armour_plates.get_mut(&Id(2)).unwrap().damaged = false;
+        assert!(!stack.can_act());
+    }
+}
@@ -0,0 +1,260 @@
+// Copyright 2023 Justin Hu
+//
+// This file is part of the Solar Dawn Server.
+//
+// The Solar Dawn Server is free software: you can redistribute it and/or
+// modify it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the License,
+// or (at your option) any later version.
+//
+// The Solar Dawn Server is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero
+// General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with the Solar Dawn Server. If not, see <https://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::state::{GameState, Owner, TurnPhase};
+
+/// An orders packet received but not yet resolved, kept so a restarted server doesn't lose it
+#[derive(Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub player: Owner,
+    pub number: u64,
+    pub phase: TurnPhase,
+    pub orders: String,
+}
+
+pub fn journal_filename(save_filename: &str) -> String {
+    format!("{save_filename}.journal")
+}
+
+/// Appends the entry and waits for it to reach the disk
+pub fn append(filename: &str, entry: &JournalEntry) -> Result<(), &'static str> {
+    let mut line = serde_json::to_string(entry).map_err(|_| "could not serialize orders")?;
+    line.push('\n');
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(filename)
+        .map_err(|_| "could not open journal")?;
+    file.write_all(line.as_bytes())
+        .map_err(|_| "could not write to journal")?;
+    file.sync_all().map_err(|_| "could not write to journal")
+}
+
+/// Reads all entries, oldest first; a missing journal has no entries
+///
+/// A crash in the middle of an append leaves a partial last line - those orders were never
+/// acknowledged, so the line is skipped. Anything unreadable before that is an error.
+pub fn read(filename: &str) -> Result<Vec<JournalEntry>, &'static str> {
+    match fs::read_to_string(filename) {
+        Ok(file) => {
+            let lines: Vec<&str> = file.lines().collect();
+            let mut entries = Vec::new();
+            for (index, line) in lines.iter().enumerate() {
+                match serde_json::from_str(line) {
+                    Ok(entry) => entries.push(entry),
+                    Err(_) if index == lines.len() - 1 => {
+                        eprintln!("warning: ignoring incomplete last entry in {filename}")
+                    }
+                    Err(_) => return Err("could not parse journal"),
+                }
+            }
+            Ok(entries)
+        }
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(_) => Err("could not read journal"),
+    }
+}
+
+/// Cuts off a partial last line left by a crash, so later appends don't get joined onto it
+pub fn discard_partial_entry(filename: &str) -> Result<(), &'static str> {
+    let contents = match fs::read(filename) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(_) => return Err("could not read journal"),
+    };
+    if contents.last().is_none_or(|last| *last == b'\n') {
+        return Ok(());
+    }
+
+    let complete_len = contents
+        .iter()
+        .rposition(|byte| *byte == b'\n')
+        .map_or(0, |last_newline| last_newline + 1);
+    let file = OpenOptions::new()
+        .write(true)
+        .open(filename)
+        .map_err(|_| "could not open journal")?;
+    file.set_len(complete_len as u64)
+        .map_err(|_| "could not truncate journal")?;
+    file.sync_all().map_err(|_| "could not truncate journal")
+}
+
+/// Parses the entries still pending in the current turn and phase; a player's latest entry wins
+pub fn replay<T>(
+    entries: Vec<JournalEntry>,
+    game_state: &GameState,
+    parse: impl Fn(&str) -> Result<T, &'static str>,
+) -> BTreeMap<Owner, T> {
+    let mut orders = BTreeMap::new();
+    for entry in entries {
+        if !game_state.is_current_turn(entry.number, &entry.phase) {
+            continue;
+        }
+
+        match parse(&entry.orders) {
+            Ok(player_orders) => {
+                orders.insert(entry.player, player_orders);
+            }
+            Err(message) => {
+                eprintln!(
+                    "warning: could not replay orders from player {}: {message}",
+                    entry.player
+                );
+            }
+        }
+    }
+    orders
+}
+
+/// Discards all entries - call once the orders in it have been resolved and saved
+pub fn clear(filename: &str) {
+    match fs::remove_file(filename) {
+        Ok(_) => (),
+        Err(err) if err.kind() == ErrorKind::NotFound => (),
+        Err(_) => {
+            eprintln!("warning: unable to clear {filename} - old orders may be replayed on restart")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    fn temp_journal(name: &str) -> String {
+        let filename = env::temp_dir()
+            .join(format!("solar_dawn_{name}_{}.journal", std::process::id()))
+            .to_str()
+            .unwrap()
+            .to_owned();
+        clear(&filename);
+        filename
+    }
+
+    fn entry(player: u8, number: u64, phase: TurnPhase, orders: &str) -> JournalEntry {
+        JournalEntry {
+            player: player.try_into().unwrap(),
+            number,
+            phase,
+            orders: orders.to_owned(),
+        }
+    }
+
+    #[test]
+    fn appended_entries_are_read_back_in_order() {
+        let filename = temp_journal("round_trip");
+        assert!(read(&filename).unwrap().is_empty());
+
+        append(&filename, &entry(0, 3, TurnPhase::Combat, "first\norders")).unwrap();
+        append(&filename, &entry(1, 3, TurnPhase::Combat, "second")).unwrap();
+        let entries = read(&filename).unwrap();
+        clear(&filename);
+
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].player == 0.try_into().unwrap());
+        assert_eq!(entries[0].number, 3);
+        assert!(entries[0].phase == TurnPhase::Combat);
+        assert_eq!(entries[0].orders, "first\norders");
+        assert!(entries[1].player == 1.try_into().unwrap());
+        assert_eq!(entries[1].orders, "second");
+        assert!(read(&filename).unwrap().is_empty());
+    }
+
+    #[test]
+    fn partial_last_entry_is_skipped_and_discarded() {
+        let filename = temp_journal("partial_entry");
+        append(&filename, &entry(0, 0, TurnPhase::Economic, "kept")).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&filename).unwrap();
+        file.write_all(br#"{"player":1,"num"#).unwrap();
+        drop(file);
+
+        assert_eq!(read(&filename).unwrap().len(), 1);
+
+        discard_partial_entry(&filename).unwrap();
+        append(
+            &filename,
+            &entry(1, 0, TurnPhase::Economic, "after restart"),
+        )
+        .unwrap();
+        let entries = read(&filename).unwrap();
+        clear(&filename);
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].orders, "kept");
+        assert_eq!(entries[1].orders, "after restart");
+    }
+
+    #[test]
+    fn corruption_before_the_last_entry_is_an_error() {
+        let filename = temp_journal("corrupt_entry");
+        fs::write(&filename, "garbage\n").unwrap();
+        append(&filename, &entry(0, 0, TurnPhase::Economic, "orders")).unwrap();
+
+        let result = read(&filename);
+        clear(&filename);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn replay_restores_latest_current_orders_once_per_player() {
+        let game_state = GameState::new(3).unwrap();
+        let entries = vec![
+            entry(0, 0, TurnPhase::Economic, "first try"),
+            entry(1, 0, TurnPhase::Economic, "only try"),
+            entry(0, 0, TurnPhase::Economic, "second try"),
+            // left over from a phase that already resolved
+            entry(2, 0, TurnPhase::Movement, "stale"),
+        ];
+
+        let orders = replay(entries, &game_state, |orders| Ok(orders.to_owned()));
+
+        assert_eq!(orders.len(), 2);
+        assert_eq!(orders[&0.try_into().unwrap()], "second try");
+        assert_eq!(orders[&1.try_into().unwrap()], "only try");
+    }
+
+    #[test]
+    fn replay_skips_unparseable_orders() {
+        let game_state = GameState::new(2).unwrap();
+        let entries = vec![
+            entry(0, 0, TurnPhase::Economic, "good"),
+            entry(1, 0, TurnPhase::Economic, "bad"),
+        ];
+
+        let orders = replay(entries, &game_state, |orders| match orders {
+            "good" => Ok(()),
+            _ => Err("bad orders"),
+        });
+
+        assert_eq!(orders.len(), 1);
+        assert!(orders.contains_key(&0.try_into().unwrap()));
+    }
+}
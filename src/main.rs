@@ -18,16 +18,18 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
 use std::{
-    collections::HashMap,
+    collections::BTreeMap,
     env, fs,
     mem::take,
-    net::{TcpListener, TcpStream},
+    net::{Shutdown, TcpListener, TcpStream},
+    panic::{self, AssertUnwindSafe},
     process::ExitCode,
     sync::{
-        mpsc::{channel, Sender},
+        mpsc::{channel, RecvTimeoutError, Sender},
         Arc, Mutex,
     },
     thread::spawn,
+    time::{Duration, Instant},
 };
 
 use game::state::GameState;
@@ -41,22 +43,31 @@ use tungstenite::{
 
 use crate::{
     game::{
-        order::{parse_orders, Order},
+        order::{parse_orders, parse_orders_header, Order},
         state::Owner,
     },
+    journal::{journal_filename, JournalEntry},
     semaphore::Semaphore,
 };
 
 type TlsWebSocket = WebSocket<TlsStream<TcpStream>>;
 
+/// how long a new connection has to finish its handshakes and send its login packet
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(10);
+/// minimum time between two full state resends requested by the same client
+const RESYNC_INTERVAL: Duration = Duration::from_secs(5);
+
 pub mod game;
+pub mod journal;
 pub mod semaphore;
 pub mod vec2;
 
 fn display_usage(name: &str) {
     eprintln!("usage:");
     eprintln!("  {name} new <filename> <player_count>");
-    eprintln!("  {name} load <filename>");
+    eprintln!("  {name} load <filename> [--read-only]");
+    eprintln!("  {name} check <filename>");
+    eprintln!("  {name} pending <filename>");
 }
 
 fn display_cert_hint() {
@@ -67,6 +78,18 @@ fn display_cert_hint() {
     eprintln!("info: and using an empty password");
 }
 
+/// Does the certificate file grant any access to anyone other than its owner?
+#[cfg(unix)]
+fn identity_is_exposed(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.permissions().mode() & 0o077 != 0
+}
+#[cfg(not(unix))]
+fn identity_is_exposed(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
 fn main() -> ExitCode {
     println!("Solar Dawn version 0.1.0");
     println!("Copyright 2024 Justin Hu");
@@ -85,7 +108,7 @@ fn main() -> ExitCode {
     }
 
     // setup game state
-    let (game_state, filename) = match args[1].as_str() {
+    let (game_state, filename, read_only) = match args[1].as_str() {
         "new" => {
             if args.len() != 4 {
                 display_usage(&args[0]);
@@ -94,33 +117,112 @@ fn main() -> ExitCode {
 
             if let Ok(num_players) = args[3].parse::<u8>() {
                 if let Ok(initial_state) = GameState::new(num_players) {
-                    initial_state.save_to_file(&args[2]);
-                    (initial_state, &args[2])
+                    if initial_state.save_to_file(&args[2]).is_ok() {
+                        journal::clear(&journal_filename(&args[2]));
+                    }
+                    (initial_state, &args[2], false)
                 } else {
                     eprintln!(
-                        "error: invalid number of players - expected a number between 2 and 6, but got {}",
+                        "error: invalid number of players - expected a number between {} and {}, but got {}",
+                        GameState::MIN_PLAYERS,
+                        GameState::MAX_PLAYERS,
                         &args[3]
                     );
                     return ExitCode::FAILURE;
                 }
             } else {
-                eprintln!("error: could not parse number of players - expected a number between 2 and 6, but got {}", &args[3]);
+                eprintln!(
+                    "error: could not parse number of players - expected a number between {} and {}, but got {}",
+                    GameState::MIN_PLAYERS,
+                    GameState::MAX_PLAYERS,
+                    &args[3]
+                );
                 return ExitCode::FAILURE;
             }
         }
         "load" => {
+            let read_only = match args.len() {
+                3 => false,
+                4 if args[3] == "--read-only" => true,
+                _ => {
+                    display_usage(&args[0]);
+                    return ExitCode::FAILURE;
+                }
+            };
+
+            match GameState::load_from_file(&args[2]) {
+                Ok(state) => (state, &args[2], read_only),
+                Err(message) => {
+                    eprintln!("error: could not parse save file: {message}");
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        "check" => {
             if args.len() != 3 {
                 display_usage(&args[0]);
                 return ExitCode::FAILURE;
             }
 
-            match GameState::load_from_file(&args[2]) {
-                Ok(state) => (state, &args[2]),
+            return match GameState::load_from_file(&args[2]) {
+                Ok(mut state) => {
+                    println!("{}", state.summary());
+                    // smoke-test resolution on the loaded copy - nothing gets saved
+                    // parts of resolution are still unimplemented, so report panics instead of crashing
+                    panic::set_hook(Box::new(|_| ()));
+                    let resolved = panic::catch_unwind(AssertUnwindSafe(|| {
+                        state.process_orders(&BTreeMap::new())
+                    }));
+                    let _ = panic::take_hook();
+                    if let Err(payload) = resolved {
+                        let message = payload
+                            .downcast_ref::<&str>()
+                            .copied()
+                            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                            .unwrap_or("unknown error");
+                        eprintln!("error: could not resolve the current phase: {message}");
+                        return ExitCode::FAILURE;
+                    }
+                    println!("info: save file is valid");
+                    ExitCode::SUCCESS
+                }
+                Err(message) => {
+                    eprintln!("error: could not parse save file: {message}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        "pending" => {
+            if args.len() != 3 {
+                display_usage(&args[0]);
+                return ExitCode::FAILURE;
+            }
+
+            let state = match GameState::load_from_file(&args[2]) {
+                Ok(state) => state,
                 Err(message) => {
                     eprintln!("error: could not parse save file: {message}");
                     return ExitCode::FAILURE;
                 }
+            };
+            let entries = match journal::read(&journal_filename(&args[2])) {
+                Ok(entries) => entries,
+                Err(message) => {
+                    eprintln!("error: could not read journal: {message}");
+                    return ExitCode::FAILURE;
+                }
+            };
+
+            let pending = journal::replay(entries, &state, |orders| Ok(orders.to_owned()));
+
+            println!("{}", state.summary());
+            for player in state.player_ids() {
+                match pending.get(&player) {
+                    Some(orders) => println!("orders from player {player}:\n{orders}"),
+                    None => println!("player {player} has not submitted orders"),
+                }
             }
+            return ExitCode::SUCCESS;
         }
         _ => {
             display_usage(&args[0]);
@@ -128,10 +230,46 @@ fn main() -> ExitCode {
         }
     };
 
+    if read_only {
+        println!("info: read-only mode - the game will not be saved");
+    }
+
+    // replay orders received for this phase before the server last stopped
+    let journal_filename = journal_filename(filename);
+    if !read_only {
+        if let Err(message) = journal::discard_partial_entry(&journal_filename) {
+            eprintln!("error: could not repair {journal_filename}: {message}");
+            return ExitCode::FAILURE;
+        }
+    }
+    let orders = match journal::read(&journal_filename) {
+        Ok(entries) => journal::replay(entries, &game_state, parse_orders),
+        Err(message) => {
+            eprintln!("error: could not replay {journal_filename}: {message}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if !orders.is_empty() {
+        println!("info: replayed orders from {} player(s)", orders.len());
+    }
+
     // set up websocket server
     let password = Alphanumeric.sample_string(&mut rand::thread_rng(), 16);
     println!("info: password is {password}");
 
+    match fs::metadata("cert.p12") {
+        Ok(metadata) if identity_is_exposed(&metadata) => {
+            eprintln!("error: cert.p12 contains the private key but is accessible by other users");
+            eprintln!("info: try running `chmod 600 cert.p12`");
+            return ExitCode::FAILURE;
+        }
+        Ok(_) => (),
+        Err(err) => {
+            eprintln!("error: could not read certificate: {err}");
+            display_cert_hint();
+            return ExitCode::FAILURE;
+        }
+    }
     let identity = match fs::read("cert.p12") {
         Ok(identity) => identity,
         Err(err) => {
@@ -170,12 +308,10 @@ fn main() -> ExitCode {
     let (termination_sender, termination_receiver) = channel();
     struct ServerState {
         game_state: GameState,
-        orders: HashMap<Owner, Vec<Order>>,
+        orders: BTreeMap<Owner, Vec<Order>>,
     }
-    let game_state: Arc<Mutex<ServerState>> = Arc::new(Mutex::new(ServerState {
-        game_state,
-        orders: HashMap::new(),
-    }));
+    let game_state: Arc<Mutex<ServerState>> =
+        Arc::new(Mutex::new(ServerState { game_state, orders }));
     'acceptor: for stream in listener.incoming() {
         match stream {
             Ok(stream) => {
@@ -185,6 +321,7 @@ fn main() -> ExitCode {
                 let game_state = game_state.clone();
                 let orders_semaphore = orders_semaphore.clone();
                 let filename = filename.clone();
+                let journal_filename = journal_filename.clone();
                 spawn(move || {
                     fn terminated(termination_sender: &Sender<Result<(), ()>>) {
                         termination_sender.send(Err(())).expect(
@@ -226,6 +363,24 @@ fn main() -> ExitCode {
                         }
                     }
 
+                    // don't let a connection that never logs in hold a player slot forever -
+                    // shut the socket down at the deadline unless the login finished first
+                    let watchdog_stream = match stream.try_clone() {
+                        Ok(watchdog_stream) => watchdog_stream,
+                        Err(err) => {
+                            eprintln!("warning: could not set login timeout: {err}");
+                            terminated(&termination_sender);
+                            return;
+                        }
+                    };
+                    let (login_done_sender, login_done_receiver) = channel::<()>();
+                    spawn(move || {
+                        if let Err(RecvTimeoutError::Timeout) =
+                            login_done_receiver.recv_timeout(LOGIN_TIMEOUT)
+                        {
+                            let _ = watchdog_stream.shutdown(Shutdown::Both);
+                        }
+                    });
                     let stream = match acceptor.accept(stream) {
                         Ok(stream) => stream,
                         Err(err) => {
@@ -274,6 +429,7 @@ fn main() -> ExitCode {
 
                             // if logged in successfully
                             let username = parts[1];
+                            drop(login_done_sender);
 
                             // send assigned player id
                             let mut game_state_locked =
@@ -289,37 +445,127 @@ fn main() -> ExitCode {
                                         terminated(&termination_sender);
                                     }
 
+                                    let mut last_resync: Option<Instant> = None;
+                                    let mut resend_state = true;
+
                                     // while game isn't over
                                     loop {
-                                        // send game state
-                                        let game_state_locked =
-                                            game_state.lock().expect("workers should not panic");
-
-                                        let serialized_state = game_state_locked
-                                            .game_state
-                                            .serialize_for_player(player);
-
-                                        drop(game_state_locked);
-
-                                        if let Err(message) =
-                                            send_message(&mut websocket, (&serialized_state).into())
-                                        {
-                                            eprintln!("warning: connection interrupted: {message}");
-                                            terminated(&termination_sender);
-                                        }
+                                        // send game state, unless the client was told its resync came too soon
+                                        if resend_state {
+                                            let game_state_locked = game_state
+                                                .lock()
+                                                .expect("workers should not panic");
+
+                                            let serialized_state = game_state_locked
+                                                .game_state
+                                                .serialize_for_player(player);
+
+                                            drop(game_state_locked);
+
+                                            if let Err(message) = send_message(
+                                                &mut websocket,
+                                                (&serialized_state).into(),
+                                            ) {
+                                                eprintln!(
+                                                    "warning: connection interrupted: {message}"
+                                                );
+                                                terminated(&termination_sender);
+                                            }
 
-                                        if serialized_state.is_terminal() {
-                                            break;
+                                            if serialized_state.is_terminal() {
+                                                break;
+                                            }
                                         }
+                                        resend_state = true;
 
                                         // get orders
                                         match recv(&mut websocket) {
-                                            Ok(player_orders) => {
-                                                match parse_orders(&player_orders) {
-                                                    Ok(player_orders) => {
+                                            Ok(request) if request == "resync" => {
+                                                // client lost track of the state - send it again
+                                                if last_resync.is_some_and(|last_resync| {
+                                                    last_resync.elapsed() < RESYNC_INTERVAL
+                                                }) {
+                                                    // the client already has the latest state - keep waiting for its orders
+                                                    try_send(
+                                                        &mut websocket,
+                                                        "resync too soon".to_owned(),
+                                                    );
+                                                    eprintln!("info: ignoring resync request from player {player} - too soon after the last one");
+                                                    resend_state = false;
+                                                    continue;
+                                                }
+                                                last_resync = Some(Instant::now());
+                                            }
+                                            Ok(packet) => {
+                                                match parse_orders_header(&packet).and_then(
+                                                    |(number, phase, orders_text)| {
+                                                        Ok((
+                                                            number,
+                                                            phase,
+                                                            orders_text,
+                                                            parse_orders(orders_text)?,
+                                                        ))
+                                                    },
+                                                ) {
+                                                    Ok((
+                                                        number,
+                                                        phase,
+                                                        orders_text,
+                                                        player_orders,
+                                                    )) => {
+                                                        // orders were written against an old state - make the client resync
+                                                        let is_current_turn = game_state
+                                                            .lock()
+                                                            .expect("workers should not panic")
+                                                            .game_state
+                                                            .is_current_turn(number, &phase);
+                                                        if !is_current_turn {
+                                                            try_send(
+                                                                &mut websocket,
+                                                                "stale orders".to_owned(),
+                                                            );
+                                                            eprintln!("info: stale orders from player {player} - resending game state");
+                                                            continue;
+                                                        }
+
+                                                        // make sure the orders survive a restart before accepting them - don't hold the lock while waiting on the disk
+                                                        if !read_only {
+                                                            if let Err(message) = journal::append(
+                                                                &journal_filename,
+                                                                &JournalEntry {
+                                                                    player,
+                                                                    number,
+                                                                    phase,
+                                                                    orders: orders_text.to_owned(),
+                                                                },
+                                                            ) {
+                                                                try_send(
+                                                                    &mut websocket,
+                                                                    "orders not saved".to_owned(),
+                                                                );
+                                                                eprintln!("warning: unable to journal orders from player {player}: {message}");
+                                                                continue;
+                                                            }
+                                                        }
+
                                                         let mut game_state_locked = game_state
                                                             .lock()
                                                             .expect("workers should not panic");
+
+                                                        // another connection for this player may have finished the turn while we were journaling
+                                                        if !game_state_locked
+                                                            .game_state
+                                                            .is_current_turn(number, &phase)
+                                                        {
+                                                            drop(game_state_locked);
+                                                            try_send(
+                                                                &mut websocket,
+                                                                "stale orders".to_owned(),
+                                                            );
+                                                            eprintln!("info: stale orders from player {player} - resending game state");
+                                                            continue;
+                                                        }
+
                                                         game_state_locked
                                                             .orders
                                                             .insert(player, player_orders);
@@ -335,12 +581,28 @@ fn main() -> ExitCode {
                                                             );
                                                             let orders =
                                                                 take(&mut game_state_locked.orders);
+                                                            let resolution_start = Instant::now();
                                                             game_state_locked
                                                                 .game_state
                                                                 .process_orders(&orders);
-                                                            game_state_locked
+                                                            let resolution_time =
+                                                                resolution_start.elapsed();
+                                                            if read_only {
+                                                                println!("info: resolved orders in {resolution_time:?}");
+                                                            } else if game_state_locked
                                                                 .game_state
-                                                                .save_to_file(&filename);
+                                                                .save_to_file(&filename)
+                                                                .is_ok()
+                                                            {
+                                                                let save_time = resolution_start
+                                                                    .elapsed()
+                                                                    - resolution_time;
+                                                                journal::clear(&journal_filename);
+                                                                println!("info: resolved orders in {resolution_time:?} and saved in {save_time:?}");
+                                                            } else {
+                                                                // keep the journal - it's the only record of these orders
+                                                                println!("info: resolved orders in {resolution_time:?}");
+                                                            }
                                                             orders_semaphore
                                                                 .up_n(num_players as u64)
                                                                 .expect("workers should not panic");
@@ -348,6 +610,11 @@ fn main() -> ExitCode {
 
                                                         drop(game_state_locked);
 
+                                                        try_send(
+                                                            &mut websocket,
+                                                            format!("orders accepted\n{number}\n{phase}"),
+                                                        );
+
                                                         // wait for updated game state
                                                         orders_semaphore
                                                             .down()
@@ -428,3 +695,32 @@ fn main() -> ExitCode {
 
     ExitCode::SUCCESS
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn identity_is_exposed_to_group_or_others() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let filename = env::temp_dir().join(format!(
+            "solar_dawn_identity_permissions_{}.p12",
+            std::process::id()
+        ));
+        fs::write(&filename, "").unwrap();
+
+        fs::set_permissions(&filename, fs::Permissions::from_mode(0o600)).unwrap();
+        let owner_only = identity_is_exposed(&fs::metadata(&filename).unwrap());
+        fs::set_permissions(&filename, fs::Permissions::from_mode(0o640)).unwrap();
+        let group_readable = identity_is_exposed(&fs::metadata(&filename).unwrap());
+        fs::set_permissions(&filename, fs::Permissions::from_mode(0o620)).unwrap();
+        let group_writable = identity_is_exposed(&fs::metadata(&filename).unwrap());
+        fs::remove_file(&filename).unwrap();
+
+        assert!(!owner_only);
+        assert!(group_readable);
+        assert!(group_writable);
+    }
+}
@@ -276,8 +276,66 @@ pub fn intercept_dynamic(
     let (ddx, ddy) = (d1x - d2x, d1y - d2y);
 
     let a = ddx.powi(2) + ddy.powi(2);
-    let b = 2.0 * (ddx * odx + ddy + ody);
+    let b = 2.0 * (ddx * odx + ddy * ody);
     let c = odx.powi(2) + ody.powi(2) - distance.powi(2);
 
     solve_intercept_quadratic(a, b, c)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: Option<f64>, expected: f64) {
+        let actual = actual.expect("should intercept");
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn static_intercept_is_where_the_line_enters_the_radius() {
+        assert_close(
+            intercept_static((0.0, 0.0), (20.0, 0.0), (10.0, 0.0), 2.0),
+            0.4,
+        );
+        assert_eq!(
+            intercept_static((0.0, 0.0), (20.0, 0.0), (10.0, 5.0), 2.0),
+            None
+        );
+    }
+
+    #[test]
+    fn dynamic_intercept_with_stationary_target_matches_static_intercept() {
+        assert_close(
+            intercept_dynamic((0.0, 0.0), (20.0, 0.0), (10.0, 0.0), (10.0, 0.0), 2.0),
+            0.4,
+        );
+    }
+
+    #[test]
+    fn dynamic_intercept_tracks_a_crossing_target() {
+        // the relative position is (t - 0.5) * (20, -10), so they meet halfway along
+        assert_close(
+            intercept_dynamic((0.0, 0.0), (20.0, 0.0), (10.0, -5.0), (10.0, 5.0), 1.0),
+            0.5 - 1.0 / 500.0_f64.sqrt(),
+        );
+    }
+
+    #[test]
+    fn dynamic_intercept_misses_a_target_moving_alongside() {
+        assert_eq!(
+            intercept_dynamic((0.0, 0.0), (20.0, 0.0), (0.0, 5.0), (20.0, 5.0), 2.0),
+            None
+        );
+    }
+
+    #[test]
+    fn dynamic_intercept_is_immediate_when_already_in_range() {
+        assert_close(
+            intercept_dynamic((0.0, 0.0), (20.0, 0.0), (1.0, 1.0), (30.0, 1.0), 2.0),
+            0.0,
+        );
+    }
+}